@@ -3,39 +3,152 @@
 #[macro_use]
 extern crate napi_derive;
 
+use napi::bindgen_prelude::Float32Array;
 use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
-use napi::{Env, JsFunction};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use napi::JsFunction;
 
+pub mod mixer;
+pub mod resample;
 pub mod speaker;
 
+/// Audio backend to drive device enumeration and capture through. Maps onto
+/// `cpal::HostId`; hosts not compiled into this build are resolved back to the
+/// platform default. Selecting `Asio` only reaches an ASIO host when the crate
+/// is built with the `asio` feature, which turns on cpal's own `asio` host
+/// (built against `asio-sys`, as cpal documents); without that feature cpal has
+/// no ASIO host compiled in, so `available_hosts()` never lists it and the
+/// request falls back to the default host.
+#[napi]
+pub enum AudioHost {
+    Default,
+    Wasapi,
+    Asio,
+    Alsa,
+    CoreAudio,
+}
+
+impl AudioHost {
+    /// The `cpal::HostId` name this variant selects, or `None` for the default.
+    fn host_name(&self) -> Option<&'static str> {
+        match self {
+            AudioHost::Default => None,
+            AudioHost::Wasapi => Some("WASAPI"),
+            AudioHost::Asio => Some("ASIO"),
+            AudioHost::Alsa => Some("ALSA"),
+            AudioHost::CoreAudio => Some("CoreAudio"),
+        }
+    }
+}
+
+/// Resolve the requested host, falling back to `cpal::default_host()` when the
+/// backend is unspecified or not available in this build.
+pub(crate) fn resolve_host(host: Option<AudioHost>) -> cpal::Host {
+    if let Some(name) = host.as_ref().and_then(AudioHost::host_name) {
+        for id in cpal::available_hosts() {
+            if id.name().eq_ignore_ascii_case(name) {
+                if let Ok(host) = cpal::host_from_id(id) {
+                    return host;
+                }
+            }
+        }
+    }
+    cpal::default_host()
+}
+
+/// List the audio host backends compiled into this build, so a JS UI can offer
+/// a backend picker (e.g. WASAPI vs ASIO on Windows).
+#[napi]
+pub fn get_available_hosts() -> Vec<String> {
+    cpal::available_hosts().into_iter().map(|id| id.name().to_string()).collect()
+}
+
+/// Delivery format for captured frames.
+#[napi]
+pub enum OutputFormat {
+    /// Little-endian i16 PCM delivered as a `Buffer` (historical default).
+    Int16Pcm,
+    /// Raw `f32` samples delivered as a `Float32Array`, with no i16 round-trip.
+    Float32,
+}
+
+/// Build a threadsafe function that converts each `Vec<f32>` frame to
+/// little-endian i16 PCM, delivered to JS as a `Buffer`.
+fn pcm_threadsafe_function(
+    callback: JsFunction,
+) -> napi::Result<ThreadsafeFunction<Vec<f32>, ErrorStrategy::Fatal>> {
+    callback.create_threadsafe_function(0, |ctx| {
+        let vec: Vec<f32> = ctx.value;
+        let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
+        for sample in vec {
+            let s = (sample * 32767.0f32).clamp(-32768.0, 32767.0) as i16;
+            pcm_bytes.extend_from_slice(&s.to_le_bytes());
+        }
+        Ok(vec![pcm_bytes])
+    })
+}
+
+/// Build a threadsafe function that passes each `Vec<f32>` frame straight
+/// through as a `Float32Array`, avoiding the lossy i16 conversion for ML
+/// pipelines that want float audio.
+fn float_threadsafe_function(
+    callback: JsFunction,
+) -> napi::Result<ThreadsafeFunction<Vec<f32>, ErrorStrategy::Fatal>> {
+    callback.create_threadsafe_function(0, |ctx| Ok(vec![Float32Array::new(ctx.value)]))
+}
+
+/// Wrap the chosen delivery format into a frame sink for the capture threads.
+fn make_sink(
+    callback: JsFunction,
+    format: Option<OutputFormat>,
+) -> napi::Result<Box<dyn FnMut(Vec<f32>) + Send>> {
+    match format.unwrap_or(OutputFormat::Int16Pcm) {
+        OutputFormat::Int16Pcm => {
+            let tsfn = pcm_threadsafe_function(callback)?;
+            Ok(Box::new(move |frame| {
+                tsfn.call(frame, ThreadsafeFunctionCallMode::NonBlocking);
+            }))
+        }
+        OutputFormat::Float32 => {
+            let tsfn = float_threadsafe_function(callback)?;
+            Ok(Box::new(move |frame| {
+                tsfn.call(frame, ThreadsafeFunctionCallMode::NonBlocking);
+            }))
+        }
+    }
+}
+
 #[napi]
 pub struct SystemAudioCapture {
-    stop_signal: Arc<Mutex<bool>>,
-    capture_thread: Option<thread::JoinHandle<()>>,
-    // device_id: Option<String>, // No longer needed if we store input
     sample_rate: u32,
-    input: Option<speaker::SpeakerInput>,
+    channels: u16,
+    format: OutputFormat,
+    input: speaker::SpeakerInput,
 }
 
 #[napi]
 impl SystemAudioCapture {
     #[napi(constructor)]
-    pub fn new(device_id: Option<String>) -> napi::Result<Self> {
-        let input = match speaker::SpeakerInput::new(device_id) {
+    pub fn new(
+        device_id: Option<String>,
+        target_sample_rate: Option<u32>,
+        preserve_channels: Option<bool>,
+        host: Option<AudioHost>,
+        format: Option<OutputFormat>,
+    ) -> napi::Result<Self> {
+        let host = resolve_host(host);
+        let format = format.unwrap_or(OutputFormat::Int16Pcm);
+        let input = match speaker::SpeakerInput::new(&host, device_id, target_sample_rate, preserve_channels.unwrap_or(false)) {
             Ok(i) => i,
             Err(e) => return Err(napi::Error::from_reason(format!("Failed to create speaker input: {}", e))),
         };
-        let sample_rate = input.sample_rate() as u32;
-        
+        let sample_rate = input.sample_rate();
+        let channels = input.channels();
+
         Ok(SystemAudioCapture {
-            stop_signal: Arc::new(Mutex::new(false)),
-            capture_thread: None,
             sample_rate,
-            input: Some(input),
-            // device_id,
+            channels,
+            format,
+            input,
         })
     }
 
@@ -45,50 +158,24 @@ impl SystemAudioCapture {
     }
 
     #[napi]
-    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
-        let tsfn: ThreadsafeFunction<Vec<f32>, ErrorStrategy::Fatal> = callback
-            .create_threadsafe_function(0, |ctx| {
-                let vec: Vec<f32> = ctx.value;
-                let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
-                for sample in vec {
-                    let s = (sample * 32767.0f32).clamp(-32768.0, 32767.0) as i16;
-                    pcm_bytes.extend_from_slice(&s.to_le_bytes());
-                }
-                Ok(vec![pcm_bytes])
-            })?;
-
-        *self.stop_signal.lock().unwrap() = false;
-        let stop_signal = self.stop_signal.clone();
-        
-        let mut input = self.input.take().ok_or_else(|| napi::Error::from_reason("Capture already started or input missing"))?;
-
-        self.capture_thread = Some(thread::spawn(move || {
-            let mut stream = input.stream();
-            
-            loop {
-                if *stop_signal.lock().unwrap() {
-                    break;
-                }
-                
-                let samples = stream.read_chunk(4800); 
-                
-                if !samples.is_empty() {
-                    tsfn.call(samples, ThreadsafeFunctionCallMode::Blocking);
-                }
-                
-                thread::sleep(Duration::from_millis(10));
-            }
-        }));
+    pub fn get_channels(&self) -> u32 {
+        self.channels as u32
+    }
 
-        Ok(())
+    #[napi]
+    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
+        // The cpal loopback callback drives delivery directly; there is no
+        // polling loop or fixed 10 ms sleep, so tight real-time consumers see
+        // only the device callback cadence plus one frame of latency.
+        let sink = make_sink(callback, Some(self.format))?;
+        self.input
+            .start(sink)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to start system audio capture: {}", e)))
     }
 
     #[napi]
     pub fn stop(&mut self) {
-        *self.stop_signal.lock().unwrap() = true;
-        if let Some(handle) = self.capture_thread.take() {
-            let _ = handle.join();
-        }
+        self.input.stop();
     }
 }
 
@@ -96,27 +183,36 @@ pub mod microphone;
 
 #[napi]
 pub struct MicrophoneCapture {
-    stop_signal: Arc<Mutex<bool>>,
-    capture_thread: Option<thread::JoinHandle<()>>,
     sample_rate: u32,
-    input: Option<microphone::MicrophoneStream>,
+    channels: u16,
+    format: OutputFormat,
+    input: microphone::MicrophoneStream,
 }
 
 #[napi]
 impl MicrophoneCapture {
     #[napi(constructor)]
-    pub fn new(device_id: Option<String>) -> napi::Result<Self> {
-        let input = match microphone::MicrophoneStream::new(device_id) {
+    pub fn new(
+        device_id: Option<String>,
+        target_sample_rate: Option<u32>,
+        preserve_channels: Option<bool>,
+        host: Option<AudioHost>,
+        format: Option<OutputFormat>,
+    ) -> napi::Result<Self> {
+        let host = resolve_host(host);
+        let format = format.unwrap_or(OutputFormat::Int16Pcm);
+        let input = match microphone::MicrophoneStream::new(&host, device_id, target_sample_rate, preserve_channels) {
             Ok(i) => i,
             Err(e) => return Err(napi::Error::from_reason(format!("Failed to create microphone input: {}", e))),
         };
         let sample_rate = input.sample_rate();
+        let channels = input.channels();
 
         Ok(MicrophoneCapture {
-            stop_signal: Arc::new(Mutex::new(false)),
-            capture_thread: None,
             sample_rate,
-            input: Some(input),
+            channels,
+            format,
+            input,
         })
     }
 
@@ -126,53 +222,24 @@ impl MicrophoneCapture {
     }
 
     #[napi]
-    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
-        let tsfn: ThreadsafeFunction<Vec<f32>, ErrorStrategy::Fatal> = callback
-            .create_threadsafe_function(0, |ctx| {
-                let vec: Vec<f32> = ctx.value;
-                let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
-                for sample in vec {
-                    let s = (sample * 32767.0f32).clamp(-32768.0, 32767.0) as i16;
-                    pcm_bytes.extend_from_slice(&s.to_le_bytes());
-                }
-                Ok(vec![pcm_bytes])
-            })?;
-
-        *self.stop_signal.lock().unwrap() = false;
-        let stop_signal = self.stop_signal.clone();
-        
-        let input = self.input.take().ok_or_else(|| napi::Error::from_reason("Capture already started or input missing"))?;
-
-        self.capture_thread = Some(thread::spawn(move || {
-            // Start playing (moved from new)
-            if let Err(e) = input.play() {
-                eprintln!("Failed to start microphone stream: {}", e);
-                return;
-            }
-
-            loop {
-                if *stop_signal.lock().unwrap() {
-                    break;
-                }
-                
-                let samples = input.read_chunk();
-                if !samples.is_empty() {
-                    tsfn.call(samples, ThreadsafeFunctionCallMode::Blocking);
-                }
-                
-                thread::sleep(Duration::from_millis(10));
-            }
-        }));
+    pub fn get_channels(&self) -> u32 {
+        self.channels as u32
+    }
 
-        Ok(())
+    #[napi]
+    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
+        // Delivery is driven by the cpal input callback rather than a polling
+        // loop: as soon as a full frame is staged the threadsafe function fires
+        // in non-blocking mode, removing the previous 10 ms sleep.
+        let sink = make_sink(callback, Some(self.format))?;
+        self.input
+            .start(sink)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to start microphone stream: {}", e)))
     }
 
     #[napi]
     pub fn stop(&mut self) {
-        *self.stop_signal.lock().unwrap() = true;
-        if let Some(handle) = self.capture_thread.take() {
-            let _ = handle.join();
-        }
+        self.input.stop();
     }
 }
 
@@ -183,8 +250,8 @@ pub struct AudioDeviceInfo {
 }
 
 #[napi]
-pub fn get_input_devices() -> Vec<AudioDeviceInfo> {
-    match microphone::list_input_devices() {
+pub fn get_input_devices(host: Option<AudioHost>) -> Vec<AudioDeviceInfo> {
+    match microphone::list_input_devices(&resolve_host(host)) {
         Ok(devs) => devs.into_iter().map(|(id, name)| AudioDeviceInfo { id, name }).collect(),
         Err(e) => {
             eprintln!("Failed to list input devices: {}", e);
@@ -193,9 +260,59 @@ pub fn get_input_devices() -> Vec<AudioDeviceInfo> {
     }
 }
 
+/// A supported config range reported by a device, so callers can negotiate a
+/// format up front instead of discovering it after construction.
+#[napi(object)]
+pub struct SupportedConfigRange {
+    pub channels: u32,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+fn to_config_ranges(ranges: Vec<(u16, u32, u32, String)>) -> Vec<SupportedConfigRange> {
+    ranges
+        .into_iter()
+        .map(|(channels, min_sample_rate, max_sample_rate, sample_format)| SupportedConfigRange {
+            channels: channels as u32,
+            min_sample_rate,
+            max_sample_rate,
+            sample_format,
+        })
+        .collect()
+}
+
+#[napi]
+pub fn get_input_supported_configs(
+    device_id: Option<String>,
+    host: Option<AudioHost>,
+) -> Vec<SupportedConfigRange> {
+    match microphone::list_input_configs(&resolve_host(host), device_id) {
+        Ok(ranges) => to_config_ranges(ranges),
+        Err(e) => {
+            eprintln!("Failed to list input configs: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[napi]
+pub fn get_output_supported_configs(
+    device_id: Option<String>,
+    host: Option<AudioHost>,
+) -> Vec<SupportedConfigRange> {
+    match speaker::list_output_configs(&resolve_host(host), device_id) {
+        Ok(ranges) => to_config_ranges(ranges),
+        Err(e) => {
+            eprintln!("Failed to list output configs: {}", e);
+            Vec::new()
+        }
+    }
+}
+
 #[napi]
-pub fn get_output_devices() -> Vec<AudioDeviceInfo> {
-    match speaker::list_output_devices() {
+pub fn get_output_devices(host: Option<AudioHost>) -> Vec<AudioDeviceInfo> {
+    match speaker::list_output_devices(&resolve_host(host)) {
         Ok(devs) => devs.into_iter().map(|(id, name)| AudioDeviceInfo { id, name }).collect(),
         Err(e) => {
              eprintln!("Failed to list output devices: {}", e);
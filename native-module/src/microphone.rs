@@ -1,17 +1,25 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use ringbuf::{traits::{Consumer, Producer, Split}, HeapRb, HeapProd, HeapCons};
 use std::sync::{Arc, Mutex};
-// use std::thread;
+
+use crate::resample::Resampler;
+
+/// Sink invoked with each completed mono frame. Installed by `start` and
+/// cleared by `stop`; the cpal data callback calls it directly.
+type FrameSink = Box<dyn FnMut(Vec<f32>) + Send>;
 
 pub struct MicrophoneStream {
     stream: cpal::Stream,
-    consumer: Arc<Mutex<HeapCons<f32>>>,
+    /// Rate reported to callers: the target rate when resampling, else native.
     sample_rate: u32,
+    /// Channel count delivered downstream: 1 when downmixing to mono, else the
+    /// device's native channel count.
+    channels: u16,
+    /// Destination for completed frames, shared with the data callback.
+    sink: Arc<Mutex<Option<FrameSink>>>,
 }
 
-pub fn list_input_devices() -> Result<Vec<(String, String)>> {
-    let host = cpal::default_host();
+pub fn list_input_devices(host: &cpal::Host) -> Result<Vec<(String, String)>> {
     let devices = host.input_devices()?;
     let mut list = Vec::new();
     for device in devices {
@@ -22,10 +30,40 @@ pub fn list_input_devices() -> Result<Vec<(String, String)>> {
     Ok(list)
 }
 
+/// Report the input config ranges the device advertises, as
+/// `(channels, min_rate, max_rate, sample_format)` tuples.
+pub fn list_input_configs(
+    host: &cpal::Host,
+    device_id: Option<String>,
+) -> Result<Vec<(u16, u32, u32, String)>> {
+    let device = if let Some(id) = device_id {
+        host.input_devices()?
+            .find(|d| d.name().map(|n| n == id).unwrap_or(false))
+            .ok_or_else(|| anyhow::anyhow!("Microphone not found"))?
+    } else {
+        host.default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No default microphone found"))?
+    };
+
+    let mut ranges = Vec::new();
+    for cfg in device.supported_input_configs()? {
+        ranges.push((
+            cfg.channels(),
+            cfg.min_sample_rate().0,
+            cfg.max_sample_rate().0,
+            format!("{:?}", cfg.sample_format()),
+        ));
+    }
+    Ok(ranges)
+}
+
 impl MicrophoneStream {
-    pub fn new(device_id: Option<String>) -> Result<Self> {
-        let host = cpal::default_host();
-        
+    pub fn new(
+        host: &cpal::Host,
+        device_id: Option<String>,
+        target_sample_rate: Option<u32>,
+        preserve_channels: Option<bool>,
+    ) -> Result<Self> {
         // Find input device
         let device = if let Some(id) = device_id {
             host.input_devices()?
@@ -37,107 +75,196 @@ impl MicrophoneStream {
         };
 
         let config = device.default_input_config()?;
-        let sample_rate = config.sample_rate().0;
-        let channels = config.channels();
+        let native_sample_rate = config.sample_rate().0;
+        let sample_rate = target_sample_rate.unwrap_or(native_sample_rate);
+        let native_channels = config.channels();
+        let preserve_channels = preserve_channels.unwrap_or(false);
+        // Channel count reported to callers: mono unless asked to preserve.
+        let channels = if preserve_channels { native_channels } else { 1 };
+
+        // The frame staging path runs a single mono `Resampler`. Interleaved
+        // multi-channel data can't go through it without interpolating across
+        // L/R boundaries, so reject resampling while preserving channels rather
+        // than silently corrupting the signal. Downmixed mono resamples fine.
+        if preserve_channels && native_channels > 1 && sample_rate != native_sample_rate {
+            return Err(anyhow::anyhow!(
+                "preserve_channels cannot be combined with resampling on a multi-channel device"
+            ));
+        }
 
         println!("[Microphone] Using device: {}", device.name().unwrap_or_default());
-        println!("[Microphone] Sample Rate: {}, Channels: {}", sample_rate, channels);
-
-        // Ring buffer for audio data
-        let buffer_len = 8192 * 4;
-        let rb = HeapRb::<f32>::new(buffer_len);
-        let (mut producer, consumer) = rb.split();
-        
-        let consumer = Arc::new(Mutex::new(consumer));
-        
+        println!("[Microphone] Sample Rate: {}, Channels: {}", native_sample_rate, native_channels);
+
+        // When downmixing, average every group of `native_channels` samples
+        // into one mono sample; when preserving, keep the interleaved layout.
+        let downmix = if preserve_channels { 1 } else { native_channels };
+        // One 20 ms frame at the reported rate, counted in sample-frames. When
+        // preserving channels the staged data is interleaved, so the frame spans
+        // `channels` samples per sample-frame; sizing it this way keeps every
+        // frame a whole number of sample-frames and never splits L/R mid-frame.
+        let frame_size = (sample_rate / 50).max(1) as usize * channels as usize;
+
+        let sink: Arc<Mutex<Option<FrameSink>>> = Arc::new(Mutex::new(None));
         let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
-        
+
         let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => device.build_input_stream(
-                &config.into(),
-                move |data: &[f32], _: &_| write_input_data_f32(data, &mut producer),
-                err_fn,
-                None
-            )?,
-            cpal::SampleFormat::I16 => device.build_input_stream(
-                &config.into(),
-                move |data: &[i16], _: &_| write_input_data_i16(data, &mut producer),
-                err_fn,
-                None
-            )?,
-            cpal::SampleFormat::U16 => device.build_input_stream(
-                &config.into(),
-                move |data: &[u16], _: &_| write_input_data_u16(data, &mut producer),
-                err_fn,
-                None
-            )?,
+            cpal::SampleFormat::F32 => {
+                let mut stage = FrameStager::new(native_sample_rate, sample_rate, frame_size, sink.clone());
+                device.build_input_stream(
+                    &config.into(),
+                    move |data: &[f32], _: &_| stage.push(downmix_f32(data, downmix)),
+                    err_fn,
+                    None,
+                )?
+            }
+            cpal::SampleFormat::I16 => {
+                let mut stage = FrameStager::new(native_sample_rate, sample_rate, frame_size, sink.clone());
+                device.build_input_stream(
+                    &config.into(),
+                    move |data: &[i16], _: &_| stage.push(downmix_i16(data, downmix)),
+                    err_fn,
+                    None,
+                )?
+            }
+            cpal::SampleFormat::U16 => {
+                let mut stage = FrameStager::new(native_sample_rate, sample_rate, frame_size, sink.clone());
+                device.build_input_stream(
+                    &config.into(),
+                    move |data: &[u16], _: &_| stage.push(downmix_u16(data, downmix)),
+                    err_fn,
+                    None,
+                )?
+            }
             _ => return Err(anyhow::anyhow!("Unsupported sample format")),
         };
 
-        // stream.play()?; // Don't auto play
-
         Ok(Self {
             stream,
-            consumer,
-            sample_rate
+            sample_rate,
+            channels,
+            sink,
         })
     }
 
-    pub fn play(&self) -> Result<()> {
-        self.stream.play()?;
-        Ok(())
+    pub fn channels(&self) -> u16 {
+        self.channels
     }
 
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
 
-    pub fn read_chunk(&self) -> Vec<f32> {
-        let mut consumer = self.consumer.lock().unwrap();
-        // In ringbuf 0.4, len() is on the ringbuffer or via traits. 
-        // We can just iterate or try_pop until empty or max count.
-        // But `consumer` is HeapCons which implies HeapRb?
-        // Let's use `try_pop` in a loop.
-        let mut chunk = Vec::new();
-        // Read available samples
-        while let Some(s) = consumer.try_pop() {
-            chunk.push(s);
-            if chunk.len() >= 4800 { break; } // limit chunk size to ~100ms at 48k
-        }
-        chunk
+    /// Install the frame sink and start the stream. Frames are delivered from
+    /// the cpal data callback with no intervening polling loop, so latency is
+    /// bounded by the device's own callback cadence plus one frame.
+    pub fn start(&self, sink: FrameSink) -> Result<()> {
+        *self.sink.lock().unwrap() = Some(sink);
+        self.stream.play()?;
+        Ok(())
     }
-}
 
-fn write_input_data_f32(input: &[f32], producer: &mut HeapProd<f32>) {
-    for &sample in input {
-        let _ = producer.try_push(sample);
+    /// Pause the stream and drop the sink, ending delivery.
+    pub fn stop(&self) {
+        let _ = self.stream.pause();
+        *self.sink.lock().unwrap() = None;
     }
 }
 
-fn write_input_data_i16(input: &[i16], producer: &mut HeapProd<f32>) {
-    for &sample in input {
-        let _ = producer.try_push(sample.to_f32() / i16::MAX as f32);
+/// Coalesces partial data-callback buffers into fixed-size frames, resampling
+/// to the reported rate, then hands each full frame to the sink.
+struct FrameStager {
+    resampler: Resampler,
+    frame_size: usize,
+    staging: Vec<f32>,
+    sink: Arc<Mutex<Option<FrameSink>>>,
+}
+
+impl FrameStager {
+    fn new(src_rate: u32, dst_rate: u32, frame_size: usize, sink: Arc<Mutex<Option<FrameSink>>>) -> Self {
+        FrameStager {
+            resampler: Resampler::new(src_rate, dst_rate),
+            frame_size,
+            staging: Vec::new(),
+            sink,
+        }
+    }
+
+    fn push(&mut self, mono: Vec<f32>) {
+        self.staging.extend(self.resampler.process(&mono));
+        while self.staging.len() >= self.frame_size {
+            let frame: Vec<f32> = self.staging.drain(..self.frame_size).collect();
+            if let Some(cb) = self.sink.lock().unwrap().as_mut() {
+                cb(frame);
+            }
+        }
     }
 }
 
-fn write_input_data_u16(input: &[u16], producer: &mut HeapProd<f32>) {
-    for &sample in input {
-        let _ = producer.try_push((sample.to_f32() - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0));
+/// Downmix interleaved `channels` samples to mono by averaging each frame.
+/// With `channels <= 1` the input is returned unchanged.
+fn downmix_f32(input: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return input.to_vec();
     }
+    let n = channels as usize;
+    input.chunks_exact(n).map(|frame| frame.iter().sum::<f32>() / n as f32).collect()
 }
 
-trait SampleToF32 {
-    fn to_f32(&self) -> f32;
+fn downmix_i16(input: &[i16], channels: u16) -> Vec<f32> {
+    let to_f32 = |s: i16| s as f32 / i16::MAX as f32;
+    if channels <= 1 {
+        return input.iter().map(|&s| to_f32(s)).collect();
+    }
+    let n = channels as usize;
+    input
+        .chunks_exact(n)
+        .map(|frame| frame.iter().map(|&s| to_f32(s)).sum::<f32>() / n as f32)
+        .collect()
 }
 
-impl SampleToF32 for i16 {
-    fn to_f32(&self) -> f32 {
-        *self as f32
+fn downmix_u16(input: &[u16], channels: u16) -> Vec<f32> {
+    let to_f32 = |s: u16| (s as f32 - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0);
+    if channels <= 1 {
+        return input.iter().map(|&s| to_f32(s)).collect();
     }
+    let n = channels as usize;
+    input
+        .chunks_exact(n)
+        .map(|frame| frame.iter().map(|&s| to_f32(s)).sum::<f32>() / n as f32)
+        .collect()
 }
 
-impl SampleToF32 for u16 {
-    fn to_f32(&self) -> f32 {
-        *self as f32
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mono_input_passes_through() {
+        assert_eq!(downmix_f32(&[0.25, -0.5, 1.0], 1), vec![0.25, -0.5, 1.0]);
+    }
+
+    #[test]
+    fn stereo_f32_averages_pairs() {
+        // [L, R, L, R] -> [(L+R)/2, (L+R)/2]
+        assert_eq!(downmix_f32(&[1.0, 0.0, 0.2, 0.4], 2), vec![0.5, 0.3]);
+    }
+
+    #[test]
+    fn four_channel_f32_averages_groups() {
+        let out = downmix_f32(&[1.0, 1.0, 1.0, 1.0, 0.0, 0.4, 0.4, 0.0], 4);
+        assert_eq!(out, vec![1.0, 0.2]);
+    }
+
+    #[test]
+    fn trailing_partial_frame_is_dropped() {
+        // chunks_exact ignores a dangling sample that doesn't complete a frame.
+        assert_eq!(downmix_f32(&[1.0, 0.0, 0.5], 2), vec![0.5]);
+    }
+
+    #[test]
+    fn i16_and_u16_normalize_to_unit_range() {
+        assert_eq!(downmix_i16(&[i16::MAX, i16::MAX], 2), vec![1.0]);
+        // u16 midpoint maps to ~0.
+        assert!(downmix_u16(&[u16::MAX / 2, u16::MAX / 2], 2)[0].abs() < 1e-3);
     }
 }
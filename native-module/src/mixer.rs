@@ -0,0 +1,318 @@
+use napi::bindgen_prelude::Float32Array;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::JsFunction;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A source's frame queue: `(timestamp, samples)` frames behind a lock so the
+/// capture thread can push while the mix thread drains.
+type FrameQueue = Arc<Mutex<VecDeque<(f64, Vec<f32>)>>>;
+
+/// A single input feeding the [`AudioMixer`].
+///
+/// Each source keeps its own lock-protected queue of `(timestamp, samples)`
+/// frames, pushed from whatever thread owns the underlying capture. The mixer
+/// clock decides which frames are due; anything far behind the clock is drained
+/// so a stalled source can't accumulate unbounded latency.
+struct MixerSource {
+    id: u32,
+    sample_rate: u32,
+    gain: f32,
+    queue: FrameQueue,
+}
+
+impl MixerSource {
+    /// Drop every queued frame except the newest, used when a source has fallen
+    /// so far behind the mixer clock that catching up sample-by-sample would
+    /// only add drift.
+    fn pop_latest(&mut self) {
+        let mut queue = self.queue.lock().unwrap();
+        while queue.len() > 1 {
+            queue.pop_front();
+        }
+    }
+
+    /// Sum this source into `out`, one output sample per slot, aligned to the
+    /// mixer timeline. `window_start` is the timestamp (seconds) of the first
+    /// slot and `dst_rate` the output rate, so slot `i` represents the instant
+    /// `window_start + i / dst_rate`.
+    ///
+    /// Each queued frame carries the capture timestamp of its first sample, so
+    /// the read position inside a frame is derived from the clock rather than a
+    /// running cursor: a source that joins late or skips a beat simply has no
+    /// frame covering the current instant and contributes silence there instead
+    /// of being summed out of phase with the others.
+    fn mix_into(&mut self, out: &mut [f32], window_start: f64, dst_rate: u32) {
+        let src_period = 1.0 / self.sample_rate as f64;
+        let dst_period = 1.0 / dst_rate as f64;
+        let mut queue = self.queue.lock().unwrap();
+        for (i, slot) in out.iter_mut().enumerate() {
+            let t = window_start + i as f64 * dst_period;
+            // Walk past frames that end before this instant, then decide whether
+            // the head frame covers it. Retries the *same* slot after popping so
+            // a frame boundary never costs a silent output sample.
+            loop {
+                let Some((ts, samples)) = queue.front() else {
+                    // No data left: remaining slots stay silent.
+                    return;
+                };
+                let frame_end = ts + samples.len() as f64 * src_period;
+                if t >= frame_end {
+                    // Head frame is entirely in the past; drop it and retry.
+                    queue.pop_front();
+                    continue;
+                }
+                if t < *ts {
+                    // The source has no frame due yet for this instant (late
+                    // join or gap): leave the slot silent and move on.
+                    break;
+                }
+                let pos = (t - ts) / src_period;
+                let idx = pos.floor() as usize;
+                let frac = (pos - idx as f64) as f32;
+                let a = samples[idx];
+                let b = if idx + 1 < samples.len() { samples[idx + 1] } else { a };
+                *slot += (a + (b - a) * frac) * self.gain;
+                break;
+            }
+        }
+    }
+}
+
+/// Synchronized mixer over several sources (e.g. system loopback plus one or
+/// more microphones), each fed with timestamped frames through `push_frame`,
+/// that emits a single mono stream through one JS callback.
+///
+/// Unlike running `SystemAudioCapture` and `MicrophoneCapture` side by side,
+/// the mixer gives every sample a shared timeline: a fixed 20 ms frame loop
+/// pops each source's queue by timestamp, resamples to the common rate and sums
+/// the result, so a consumer receives "everything the user heard and said" as
+/// one stream. Each output frame reaches the callback as i16 PCM only; its
+/// timestamp on the mixer timeline is not passed along.
+#[napi]
+pub struct AudioMixer {
+    target_sample_rate: u32,
+    frame_size: usize,
+    sources: Arc<Mutex<Vec<MixerSource>>>,
+    next_id: Arc<AtomicU32>,
+    stop_signal: Arc<Mutex<bool>>,
+    mix_thread: Option<thread::JoinHandle<()>>,
+}
+
+#[napi]
+impl AudioMixer {
+    /// Create a mixer that produces mono output at `target_sample_rate`.
+    #[napi(constructor)]
+    pub fn new(target_sample_rate: u32) -> Self {
+        // One frame every 20 ms at the target rate.
+        let frame_size = (target_sample_rate / 50).max(1) as usize;
+        AudioMixer {
+            target_sample_rate,
+            frame_size,
+            sources: Arc::new(Mutex::new(Vec::new())),
+            next_id: Arc::new(AtomicU32::new(0)),
+            stop_signal: Arc::new(Mutex::new(false)),
+            mix_thread: None,
+        }
+    }
+
+    #[napi]
+    pub fn get_sample_rate(&self) -> u32 {
+        self.target_sample_rate
+    }
+
+    /// Register a source producing audio at `source_sample_rate` and return its
+    /// id. `gain` scales the source into the sum, so callers can duck the mic
+    /// relative to system audio.
+    #[napi]
+    pub fn add_source(&mut self, source_sample_rate: u32, gain: f64) -> u32 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.sources.lock().unwrap().push(MixerSource {
+            id,
+            sample_rate: source_sample_rate,
+            gain: gain as f32,
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+        });
+        id
+    }
+
+    #[napi]
+    pub fn remove_source(&mut self, id: u32) {
+        self.sources.lock().unwrap().retain(|s| s.id != id);
+    }
+
+    /// Adjust a source's gain after it has been added.
+    #[napi]
+    pub fn set_source_gain(&mut self, id: u32, gain: f64) {
+        if let Some(source) = self.sources.lock().unwrap().iter_mut().find(|s| s.id == id) {
+            source.gain = gain as f32;
+        }
+    }
+
+    /// Push a frame of samples captured at `timestamp` (seconds) into the queue
+    /// for `id`. Called from JS with the frames each source's capture delivers.
+    #[napi]
+    pub fn push_frame(&mut self, id: u32, timestamp: f64, samples: Float32Array) {
+        if let Some(source) = self.sources.lock().unwrap().iter().find(|s| s.id == id) {
+            source.queue.lock().unwrap().push_back((timestamp, samples.to_vec()));
+        }
+    }
+
+    #[napi]
+    pub fn start(&mut self, callback: JsFunction) -> napi::Result<()> {
+        let tsfn: ThreadsafeFunction<Vec<f32>, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx| {
+                let vec: Vec<f32> = ctx.value;
+                let mut pcm_bytes = Vec::with_capacity(vec.len() * 2);
+                for sample in vec {
+                    let s = (sample * 32767.0f32).clamp(-32768.0, 32767.0) as i16;
+                    pcm_bytes.extend_from_slice(&s.to_le_bytes());
+                }
+                Ok(vec![pcm_bytes])
+            })?;
+
+        *self.stop_signal.lock().unwrap() = false;
+        let stop_signal = self.stop_signal.clone();
+        let sources = self.sources.clone();
+        let frame_size = self.frame_size;
+        let dst_rate = self.target_sample_rate;
+        // How far a source may lag the mixer clock before we drain it, measured
+        // in queued frames. Two frames (~40 ms) keeps latency bounded without
+        // chopping a source that is merely a frame behind.
+        let drain_frames = 2usize;
+
+        self.mix_thread = Some(thread::spawn(move || {
+            // The clock is the timestamp of the next frame's first sample. It is
+            // left unset until some source delivers data, then anchored to the
+            // earliest queued timestamp so the timeline starts with real audio
+            // rather than a run of silence.
+            let mut clock: Option<f64> = None;
+            let frame_secs = frame_size as f64 / dst_rate as f64;
+            loop {
+                if *stop_signal.lock().unwrap() {
+                    break;
+                }
+
+                let mut frame = {
+                    let mut sources = sources.lock().unwrap();
+
+                    // Drain runaway backlogs first, then re-derive the clock from
+                    // the oldest frame still queued. The loop sleeps one frame per
+                    // iteration but also spends real time mixing and calling into
+                    // JS, so a free-running `clock += frame_secs` would fall behind
+                    // the wall-clock timestamps sources stamp their frames with and
+                    // eventually drift into permanent silence. Anchoring to the
+                    // earliest queued timestamp keeps the timeline pinned to real
+                    // audio and bounds drift to the queue depth.
+                    for source in sources.iter_mut() {
+                        if source.queue.lock().unwrap().len() > drain_frames {
+                            source.pop_latest();
+                        }
+                    }
+                    let earliest = sources
+                        .iter()
+                        .filter_map(|s| s.queue.lock().unwrap().front().map(|(ts, _)| *ts))
+                        .reduce(f64::min);
+                    let start = match (clock, earliest) {
+                        // Never read behind the oldest audio still available.
+                        (Some(c), Some(ts)) => c.max(ts),
+                        (Some(c), None) => c,
+                        (None, Some(ts)) => ts,
+                        (None, None) => {
+                            // No data yet; wait for a source to push.
+                            drop(sources);
+                            thread::sleep(Duration::from_secs_f64(frame_secs));
+                            continue;
+                        }
+                    };
+
+                    let mut frame = vec![0.0f32; frame_size];
+                    for source in sources.iter_mut() {
+                        source.mix_into(&mut frame, start, dst_rate);
+                    }
+                    clock = Some(start + frame_secs);
+                    frame
+                    // `sources` lock released here, before the JS call below.
+                };
+
+                for sample in frame.iter_mut() {
+                    *sample = sample.clamp(-1.0, 1.0);
+                }
+                // Deliver without holding any lock and without blocking: a
+                // blocking call would stall the mix thread on the JS thread, and
+                // because the `sources` lock is already released, a JS callback
+                // that re-enters `push_frame`/`add_source` cannot deadlock on it.
+                tsfn.call(frame, ThreadsafeFunctionCallMode::NonBlocking);
+
+                thread::sleep(Duration::from_secs_f64(frame_secs));
+            }
+        }));
+
+        Ok(())
+    }
+
+    #[napi]
+    pub fn stop(&mut self) {
+        *self.stop_signal.lock().unwrap() = true;
+        if let Some(handle) = self.mix_thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(sample_rate: u32, gain: f32, frames: &[(f64, Vec<f32>)]) -> MixerSource {
+        let queue = VecDeque::from(frames.to_vec());
+        MixerSource {
+            id: 0,
+            sample_rate,
+            gain,
+            queue: Arc::new(Mutex::new(queue)),
+        }
+    }
+
+    #[test]
+    fn mixes_aligned_frames_sample_for_sample() {
+        // Same rate as the output, frame starting exactly at the window: every
+        // sample should pass through unchanged (gain 1.0, no silence inserted at
+        // the frame boundary).
+        let mut s = source(4, 1.0, &[(0.0, vec![1.0, 2.0, 3.0, 4.0])]);
+        let mut out = vec![0.0f32; 4];
+        s.mix_into(&mut out, 0.0, 4);
+        assert_eq!(out, vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn late_source_is_silent_until_its_timestamp_then_aligns() {
+        // Two sources stamped with staggered timestamps. The first plays from
+        // t=0, the second only has audio from t=0.5s. Read a 1 s window at 4 Hz
+        // (slots at t = 0, .25, .5, .75): the late source must contribute silence
+        // for the first two slots and its own samples, in phase, afterwards.
+        let mut early = source(4, 1.0, &[(0.0, vec![1.0, 1.0, 1.0, 1.0])]);
+        let mut late = source(4, 1.0, &[(0.5, vec![8.0, 9.0])]);
+
+        let mut out = vec![0.0f32; 4];
+        early.mix_into(&mut out, 0.0, 4);
+        late.mix_into(&mut out, 0.0, 4);
+
+        // Slots 0,1: only `early`. Slots 2,3: early + late, still aligned to the
+        // shared timeline rather than summed from the late source's head.
+        assert_eq!(out, vec![1.0, 1.0, 1.0 + 8.0, 1.0 + 9.0]);
+    }
+
+    #[test]
+    fn frame_boundary_does_not_drop_a_sample() {
+        // Two back-to-back 2-sample frames covering a contiguous 4-sample window.
+        // Crossing the boundary must not inject a silent slot.
+        let mut s = source(4, 1.0, &[(0.0, vec![1.0, 2.0]), (0.5, vec![3.0, 4.0])]);
+        let mut out = vec![0.0f32; 4];
+        s.mix_into(&mut out, 0.0, 4);
+        assert_eq!(out, vec![1.0, 2.0, 3.0, 4.0]);
+    }
+}
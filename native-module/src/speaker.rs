@@ -0,0 +1,237 @@
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::{Arc, Mutex};
+
+use crate::resample::Resampler;
+
+/// Sink invoked with each completed frame of loopback audio. Installed by
+/// `start` and cleared by `stop`; the cpal data callback calls it directly.
+type FrameSink = Box<dyn FnMut(Vec<f32>) + Send>;
+
+/// System-audio (loopback) capture, the output-side counterpart to
+/// [`crate::microphone::MicrophoneStream`]. It taps what the selected output
+/// device is playing and delivers it as fixed 20 ms frames.
+pub struct SpeakerInput {
+    stream: cpal::Stream,
+    /// Rate reported to callers: the target rate when resampling, else native.
+    sample_rate: u32,
+    /// Channel count delivered downstream: 1 when downmixing to mono, else the
+    /// device's native channel count.
+    channels: u16,
+    /// Destination for completed frames, shared with the data callback.
+    sink: Arc<Mutex<Option<FrameSink>>>,
+}
+
+pub fn list_output_devices(host: &cpal::Host) -> Result<Vec<(String, String)>> {
+    let devices = host.output_devices()?;
+    let mut list = Vec::new();
+    for device in devices {
+        if let Ok(name) = device.name() {
+            list.push((name.clone(), name));
+        }
+    }
+    Ok(list)
+}
+
+/// Report the output config ranges the device advertises, as
+/// `(channels, min_rate, max_rate, sample_format)` tuples.
+pub fn list_output_configs(
+    host: &cpal::Host,
+    device_id: Option<String>,
+) -> Result<Vec<(u16, u32, u32, String)>> {
+    let device = if let Some(id) = device_id {
+        host.output_devices()?
+            .find(|d| d.name().map(|n| n == id).unwrap_or(false))
+            .ok_or_else(|| anyhow::anyhow!("Output device not found"))?
+    } else {
+        host.default_output_device()
+            .ok_or_else(|| anyhow::anyhow!("No default output device found"))?
+    };
+
+    let mut ranges = Vec::new();
+    for cfg in device.supported_output_configs()? {
+        ranges.push((
+            cfg.channels(),
+            cfg.min_sample_rate().0,
+            cfg.max_sample_rate().0,
+            format!("{:?}", cfg.sample_format()),
+        ));
+    }
+    Ok(ranges)
+}
+
+impl SpeakerInput {
+    pub fn new(
+        host: &cpal::Host,
+        device_id: Option<String>,
+        target_sample_rate: Option<u32>,
+        preserve_channels: bool,
+    ) -> Result<Self> {
+        // Find output device to loop back from.
+        let device = if let Some(id) = device_id {
+            host.output_devices()?
+                .find(|d| d.name().map(|n| n == id).unwrap_or(false))
+                .ok_or_else(|| anyhow::anyhow!("Output device not found"))?
+        } else {
+            host.default_output_device()
+                .ok_or_else(|| anyhow::anyhow!("No default output device found"))?
+        };
+
+        let config = device.default_output_config()?;
+        let native_sample_rate = config.sample_rate().0;
+        let sample_rate = target_sample_rate.unwrap_or(native_sample_rate);
+        let native_channels = config.channels();
+        // Channel count reported to callers: mono unless asked to preserve.
+        let channels = if preserve_channels { native_channels } else { 1 };
+
+        // The frame staging path runs a single mono `Resampler`. Interleaved
+        // multi-channel data can't go through it without interpolating across
+        // L/R boundaries, so reject resampling while preserving channels rather
+        // than silently corrupting the signal. Downmixed mono resamples fine.
+        if preserve_channels && native_channels > 1 && sample_rate != native_sample_rate {
+            return Err(anyhow::anyhow!(
+                "preserve_channels cannot be combined with resampling on a multi-channel device"
+            ));
+        }
+
+        println!("[Speaker] Using device: {}", device.name().unwrap_or_default());
+        println!("[Speaker] Sample Rate: {}, Channels: {}", native_sample_rate, native_channels);
+
+        // When downmixing, average every group of `native_channels` samples
+        // into one mono sample; when preserving, keep the interleaved layout.
+        let downmix = if preserve_channels { 1 } else { native_channels };
+        // One 20 ms frame at the reported rate, counted in sample-frames. When
+        // preserving channels the staged data is interleaved, so the frame spans
+        // `channels` samples per sample-frame; sizing it this way keeps every
+        // frame a whole number of sample-frames and never splits L/R mid-frame.
+        let frame_size = (sample_rate / 50).max(1) as usize * channels as usize;
+
+        let sink: Arc<Mutex<Option<FrameSink>>> = Arc::new(Mutex::new(None));
+        let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => {
+                let mut stage = FrameStager::new(native_sample_rate, sample_rate, frame_size, sink.clone());
+                device.build_input_stream(
+                    &config.into(),
+                    move |data: &[f32], _: &_| stage.push(downmix_f32(data, downmix)),
+                    err_fn,
+                    None,
+                )?
+            }
+            cpal::SampleFormat::I16 => {
+                let mut stage = FrameStager::new(native_sample_rate, sample_rate, frame_size, sink.clone());
+                device.build_input_stream(
+                    &config.into(),
+                    move |data: &[i16], _: &_| stage.push(downmix_i16(data, downmix)),
+                    err_fn,
+                    None,
+                )?
+            }
+            cpal::SampleFormat::U16 => {
+                let mut stage = FrameStager::new(native_sample_rate, sample_rate, frame_size, sink.clone());
+                device.build_input_stream(
+                    &config.into(),
+                    move |data: &[u16], _: &_| stage.push(downmix_u16(data, downmix)),
+                    err_fn,
+                    None,
+                )?
+            }
+            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
+        };
+
+        Ok(Self {
+            stream,
+            sample_rate,
+            channels,
+            sink,
+        })
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Install the frame sink and start the loopback stream. Frames are
+    /// delivered from the cpal data callback with no intervening polling loop,
+    /// so latency is bounded by the device's own callback cadence plus one frame.
+    pub fn start(&self, sink: FrameSink) -> Result<()> {
+        *self.sink.lock().unwrap() = Some(sink);
+        self.stream.play()?;
+        Ok(())
+    }
+
+    /// Pause the stream and drop the sink, ending delivery.
+    pub fn stop(&self) {
+        let _ = self.stream.pause();
+        *self.sink.lock().unwrap() = None;
+    }
+}
+
+/// Coalesces partial data-callback buffers into fixed-size frames, resampling
+/// to the reported rate, then hands each full frame to the sink.
+struct FrameStager {
+    resampler: Resampler,
+    frame_size: usize,
+    staging: Vec<f32>,
+    sink: Arc<Mutex<Option<FrameSink>>>,
+}
+
+impl FrameStager {
+    fn new(src_rate: u32, dst_rate: u32, frame_size: usize, sink: Arc<Mutex<Option<FrameSink>>>) -> Self {
+        FrameStager {
+            resampler: Resampler::new(src_rate, dst_rate),
+            frame_size,
+            staging: Vec::new(),
+            sink,
+        }
+    }
+
+    fn push(&mut self, mono: Vec<f32>) {
+        self.staging.extend(self.resampler.process(&mono));
+        while self.staging.len() >= self.frame_size {
+            let frame: Vec<f32> = self.staging.drain(..self.frame_size).collect();
+            if let Some(cb) = self.sink.lock().unwrap().as_mut() {
+                cb(frame);
+            }
+        }
+    }
+}
+
+/// Downmix interleaved `channels` samples to mono by averaging each frame.
+/// With `channels <= 1` the input is returned unchanged.
+fn downmix_f32(input: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return input.to_vec();
+    }
+    let n = channels as usize;
+    input.chunks_exact(n).map(|frame| frame.iter().sum::<f32>() / n as f32).collect()
+}
+
+fn downmix_i16(input: &[i16], channels: u16) -> Vec<f32> {
+    let to_f32 = |s: i16| s as f32 / i16::MAX as f32;
+    if channels <= 1 {
+        return input.iter().map(|&s| to_f32(s)).collect();
+    }
+    let n = channels as usize;
+    input
+        .chunks_exact(n)
+        .map(|frame| frame.iter().map(|&s| to_f32(s)).sum::<f32>() / n as f32)
+        .collect()
+}
+
+fn downmix_u16(input: &[u16], channels: u16) -> Vec<f32> {
+    let to_f32 = |s: u16| (s as f32 - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0);
+    if channels <= 1 {
+        return input.iter().map(|&s| to_f32(s)).collect();
+    }
+    let n = channels as usize;
+    input
+        .chunks_exact(n)
+        .map(|frame| frame.iter().map(|&s| to_f32(s)).sum::<f32>() / n as f32)
+        .collect()
+}